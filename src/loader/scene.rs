@@ -1,25 +1,66 @@
+use super::ObjError;
 use anyhow::Result;
-use bevy_asset::{Handle, LoadContext, LoadedAsset};
+use bevy_asset::{AssetPath, Handle, LoadContext, LoadedAsset};
+use bevy_core::Name;
 use bevy_ecs::world::{FromWorld, World};
 use bevy_hierarchy::BuildWorldChildren;
-use bevy_pbr::{PbrBundle, StandardMaterial};
+use bevy_log::warn;
+use bevy_pbr::{AlphaMode, PbrBundle, StandardMaterial};
 use bevy_render::{
     mesh::{Indices, Mesh},
     prelude::{Color, SpatialBundle},
+    primitives::Aabb,
     render_resource::PrimitiveTopology,
     renderer::RenderDevice,
-    texture::{CompressedImageFormats, Image, ImageType},
+    texture::{CompressedImageFormats, Image, ImageFormat, ImageType},
 };
 use bevy_scene::Scene;
-use std::path::PathBuf;
-use thiserror::Error;
+use std::{cell::RefCell, collections::HashSet, path::PathBuf};
 
-fn material_label(idx: usize) -> String {
-    "Material".to_owned() + &idx.to_string()
+/// Labels the material asset by its MTL name (e.g. "MaterialWheel_FL"),
+/// falling back to its index when the MTL didn't provide one. More than one
+/// material entry can carry the same name, so `used` tracks labels already
+/// handed out and the index is appended to disambiguate repeats.
+fn material_label(name: &str, idx: usize, used: &mut HashSet<String>) -> String {
+    let label = if name.is_empty() {
+        "Material".to_owned() + &idx.to_string()
+    } else {
+        "Material".to_owned() + name
+    };
+    if used.insert(label.clone()) {
+        label
+    } else {
+        label + &idx.to_string()
+    }
+}
+
+/// Labels the mesh asset by its `o`/`g` group name (e.g. "MeshWheel_FL"),
+/// falling back to its index when the OBJ didn't provide one. tobj splits a
+/// single named object into multiple `Model`s when it spans more than one
+/// material, so `used` tracks labels already handed out and the index is
+/// appended to disambiguate repeats rather than letting a later model's
+/// mesh silently overwrite an earlier one's label.
+fn mesh_label(name: &str, idx: usize, used: &mut HashSet<String>) -> String {
+    let label = if name.is_empty() {
+        "Mesh".to_owned() + &idx.to_string()
+    } else {
+        "Mesh".to_owned() + name
+    };
+    if used.insert(label.clone()) {
+        label
+    } else {
+        label + &idx.to_string()
+    }
 }
 
-fn mesh_label(idx: usize) -> String {
-    "Mesh".to_owned() + &idx.to_string()
+/// Generates MikkTSpace tangents so normal maps render correctly. Requires
+/// indexed geometry plus position, normal and UV_0 attributes; if any are
+/// missing we log and leave the mesh without tangents rather than failing
+/// the whole load.
+fn generate_tangents(mesh: &mut Mesh, name: &str) {
+    if let Err(err) = mesh.generate_tangents() {
+        warn!("Failed to generate tangents for mesh \"{name}\": {err}");
+    }
 }
 
 impl FromWorld for super::ObjLoader {
@@ -34,18 +75,6 @@ impl FromWorld for super::ObjLoader {
     }
 }
 
-#[derive(Error, Debug)]
-pub enum ObjError {
-    #[error("Invalid OBJ file: {0}")]
-    TobjError(#[from] tobj::LoadError),
-    #[error("Invalid image file for texture: {0}")]
-    InvalidImageFile(PathBuf),
-    #[error("Asset reading failed: {0}")]
-    AssetIOError(#[from] bevy_asset::AssetIoError),
-    #[error("Texture conversion failed: {0}")]
-    TextureError(#[from] bevy_render::texture::TextureError),
-}
-
 pub(super) async fn load_obj<'a, 'b>(
     bytes: &'a [u8],
     load_context: &'a mut LoadContext<'b>,
@@ -60,35 +89,55 @@ async fn load_texture_image<'a, 'b>(
     image_path: &'a str,
     load_context: &'a mut LoadContext<'b>,
     supported_compressed_formats: CompressedImageFormats,
+    is_srgb: bool,
 ) -> Result<Image, ObjError> {
     let mut path = load_context.path().to_owned();
     path.set_file_name(image_path);
-    let extension = ImageType::Extension(
-        path.extension()
-            .and_then(|e| e.to_str())
-            .ok_or(ObjError::InvalidImageFile(path.to_path_buf()))?,
-    );
+    // Register the texture as a dependency so editing it on disk re-triggers
+    // this OBJ's load, mirroring Bevy's built-in loaders.
+    load_context.depend_on(AssetPath::new(path.clone(), None));
     let bytes = load_context.asset_io().load_path(&path).await?;
-    // TODO(luca) confirm value of is_srgb
-    let is_srgb = true;
+
+    let image_type = match path
+        .extension()
+        .and_then(|e| e.to_str())
+        .filter(|ext| ImageFormat::from_extension(ext).is_some())
+    {
+        Some(ext) => ImageType::Extension(ext),
+        // Textures referenced with no extension, an unrecognized one, or an
+        // uppercase one don't resolve through `ImageType::Extension`; sniff
+        // the magic bytes instead so still-valid images aren't rejected.
+        None => {
+            let format = infer::get(&bytes)
+                .and_then(|kind| ImageFormat::from_mime_type(kind.mime_type()))
+                .ok_or_else(|| ObjError::InvalidImageFile(path.to_path_buf()))?;
+            ImageType::Format(format)
+        }
+    };
+
     Ok(Image::from_buffer(
         &bytes,
-        extension,
+        image_type,
         supported_compressed_formats,
         is_srgb,
     )?)
 }
 
+/// Loads the OBJ's models and materials, returning the paths of any `mtllib`
+/// files it resolved along the way so the caller can register them as load
+/// dependencies.
 async fn load_obj_data<'a, 'b>(
     mut bytes: &'a [u8],
     load_context: &'a mut LoadContext<'b>,
-) -> tobj::LoadResult {
+) -> (tobj::LoadResult, Vec<PathBuf>) {
     let options = tobj::GPU_LOAD_OPTIONS;
     let asset_io = &load_context.asset_io();
     let ctx_path = load_context.path();
-    tobj::load_obj_buf_async(&mut bytes, &options, |p| async move {
+    let mtl_dependencies = RefCell::new(Vec::new());
+    let result = tobj::load_obj_buf_async(&mut bytes, &options, |p| async move {
         let mut asset_path = ctx_path.to_owned();
-        asset_path.set_file_name(p);
+        asset_path.set_file_name(&p);
+        mtl_dependencies.borrow_mut().push(asset_path.clone());
         asset_io
             .load_path(&asset_path)
             .await
@@ -96,21 +145,37 @@ async fn load_obj_data<'a, 'b>(
                 tobj::load_mtl_buf(&mut bytes.as_slice())
             })
     })
-    .await
+    .await;
+    (result, mtl_dependencies.into_inner())
 }
 
 async fn load_mat_texture<'a, 'b>(
     texture: &String,
     load_context: &'a mut LoadContext<'b>,
     supported_compressed_formats: CompressedImageFormats,
+    is_srgb: bool,
 ) -> Result<Option<Handle<Image>>, ObjError> {
     if !texture.is_empty() {
-        let handle = if load_context.has_labeled_asset(texture) {
-            load_context.get_handle(texture)
+        // The same file can be referenced from slots that need different
+        // color-space decoding (e.g. reused as both a diffuse and a
+        // metallic map), so the cache key has to include `is_srgb` too,
+        // not just the path.
+        let label = if is_srgb {
+            texture.clone()
         } else {
-            let img =
-                load_texture_image(texture, load_context, supported_compressed_formats).await?;
-            load_context.set_labeled_asset(texture, LoadedAsset::new(img))
+            texture.clone() + "#linear"
+        };
+        let handle = if load_context.has_labeled_asset(&label) {
+            load_context.get_handle(&label)
+        } else {
+            let img = load_texture_image(
+                texture,
+                load_context,
+                supported_compressed_formats,
+                is_srgb,
+            )
+            .await?;
+            load_context.set_labeled_asset(&label, LoadedAsset::new(img))
         };
         Ok(Some(handle))
     } else {
@@ -118,38 +183,150 @@ async fn load_mat_texture<'a, 'b>(
     }
 }
 
-async fn load_obj_scene<'a, 'b>(
-    bytes: &'a [u8],
+/// Reads a whitespace-separated triple (e.g. a PBR extension `Ke` line) out of
+/// `mat.unknown_param`, since tobj surfaces unrecognized MTL statements verbatim
+/// rather than parsing them.
+fn unknown_param_color(mat: &tobj::Material, key: &str) -> Option<Color> {
+    let components: Vec<f32> = mat
+        .unknown_param
+        .get(key)?
+        .split_whitespace()
+        .filter_map(|v| v.parse().ok())
+        .collect();
+    match components[..] {
+        [r, g, b] => Some(Color::rgb(r, g, b)),
+        [v] => Some(Color::rgb(v, v, v)),
+        _ => None,
+    }
+}
+
+fn unknown_param_f32(mat: &tobj::Material, key: &str) -> Option<f32> {
+    mat.unknown_param.get(key)?.trim().parse().ok()
+}
+
+fn unknown_param_texture(mat: &tobj::Material, key: &str) -> Option<&String> {
+    mat.unknown_param.get(key).filter(|path| !path.is_empty())
+}
+
+async fn load_material<'a, 'b>(
+    mat: tobj::Material,
     load_context: &'a mut LoadContext<'b>,
     supported_compressed_formats: CompressedImageFormats,
-) -> Result<Scene, ObjError> {
-    let (models, materials) = load_obj_data(bytes, load_context).await?;
-    let materials = materials?;
+) -> Result<StandardMaterial, ObjError> {
+    let mut base_color = Color::rgb(mat.diffuse[0], mat.diffuse[1], mat.diffuse[2]);
+    // `d` / `Tr` below 1.0 means the material is (partially) transparent.
+    let alpha_mode = if mat.dissolve < 1.0 {
+        base_color.set_a(mat.dissolve);
+        AlphaMode::Blend
+    } else {
+        AlphaMode::Opaque
+    };
 
-    let mut mat_handles = Vec::with_capacity(materials.len());
-    for (mat_idx, mat) in materials.into_iter().enumerate() {
-        // TODO(luca) check other material properties
-        let material = StandardMaterial {
-            base_color: Color::rgb(mat.diffuse[0], mat.diffuse[1], mat.diffuse[2]),
-            base_color_texture: load_mat_texture(
-                &mat.diffuse_texture,
+    // Map Phong shininess onto a roughness estimate, the same conversion glTF
+    // exporters use when round-tripping Phong materials.
+    let perceptual_roughness = (1.0 - (mat.shininess.clamp(0.0, 1000.0) / 1000.0)).clamp(0.0, 1.0);
+    // A `Pm` (PBR extension) value is authoritative; otherwise approximate
+    // metalness from how strong the specular highlight is.
+    let metallic = unknown_param_f32(&mat, "Pm")
+        .unwrap_or_else(|| (mat.specular.iter().sum::<f32>() / 3.0).clamp(0.0, 1.0));
+    let emissive = unknown_param_color(&mat, "Ke").unwrap_or(Color::BLACK);
+
+    // `base_color` and `emissive` are perceptual colors and need sRGB
+    // decoding; every other slot here carries linear PBR data (roughness,
+    // metallic, occlusion, tangent-space normals) and must not be.
+    let base_color_texture = load_mat_texture(
+        &mat.diffuse_texture,
+        load_context,
+        supported_compressed_formats,
+        true,
+    )
+    .await?;
+    let normal_map_texture = load_mat_texture(
+        &mat.normal_texture,
+        load_context,
+        supported_compressed_formats,
+        false,
+    )
+    .await?;
+    // `map_Pm` is the PBR extension's metallic map; fall back to the Phong
+    // specular/shininess maps when it isn't present.
+    let metallic_roughness_texture = match unknown_param_texture(&mat, "map_Pm") {
+        Some(path) => {
+            load_mat_texture(path, load_context, supported_compressed_formats, false).await?
+        }
+        None if !mat.specular_texture.is_empty() => {
+            load_mat_texture(
+                &mat.specular_texture,
                 load_context,
                 supported_compressed_formats,
+                false,
             )
-            .await?,
-            normal_map_texture: load_mat_texture(
-                &mat.normal_texture,
+            .await?
+        }
+        None => {
+            load_mat_texture(
+                &mat.shininess_texture,
                 load_context,
                 supported_compressed_formats,
+                false,
             )
-            .await?,
-            ..Default::default()
-        };
-        mat_handles.push(load_context.set_labeled_asset(&material_label(mat_idx), LoadedAsset::new(material)));
+            .await?
+        }
+    };
+    let occlusion_texture = load_mat_texture(
+        &mat.dissolve_texture,
+        load_context,
+        supported_compressed_formats,
+        false,
+    )
+    .await?;
+    let emissive_texture = match unknown_param_texture(&mat, "map_Ke") {
+        Some(path) => {
+            load_mat_texture(path, load_context, supported_compressed_formats, true).await?
+        }
+        None => None,
+    };
+
+    Ok(StandardMaterial {
+        base_color,
+        base_color_texture,
+        emissive,
+        emissive_texture,
+        perceptual_roughness,
+        metallic,
+        metallic_roughness_texture,
+        normal_map_texture,
+        occlusion_texture,
+        alpha_mode,
+        ..Default::default()
+    })
+}
+
+async fn load_obj_scene<'a, 'b>(
+    bytes: &'a [u8],
+    load_context: &'a mut LoadContext<'b>,
+    supported_compressed_formats: CompressedImageFormats,
+) -> Result<Scene, ObjError> {
+    let (obj, mtl_dependencies) = load_obj_data(bytes, load_context).await;
+    for path in mtl_dependencies {
+        load_context.depend_on(AssetPath::new(path, None));
+    }
+    let (models, materials) = obj?;
+    let materials = materials?;
+
+    let mut mat_handles = Vec::with_capacity(materials.len());
+    let mut mat_has_normal_map = Vec::with_capacity(materials.len());
+    let mut used_material_labels = HashSet::with_capacity(materials.len());
+    for (mat_idx, mat) in materials.into_iter().enumerate() {
+        let label = material_label(&mat.name, mat_idx, &mut used_material_labels);
+        let material = load_material(mat, load_context, supported_compressed_formats).await?;
+        mat_has_normal_map.push(material.normal_map_texture.is_some());
+        mat_handles.push(load_context.set_labeled_asset(&label, LoadedAsset::new(material)));
     }
 
     let mut world = World::default();
     let world_id = world.spawn(SpatialBundle::INHERITED_IDENTITY).id();
+    let mut used_mesh_labels = HashSet::with_capacity(models.len());
     for (model_idx, model) in models.into_iter().enumerate() {
         let vertex_position: Vec<[f32; 3]> = model
             .mesh
@@ -183,10 +360,27 @@ async fn load_obj_scene<'a, 'b>(
         } else {
             mesh.duplicate_vertices();
             mesh.compute_flat_normals();
+            // `duplicate_vertices` takes the index buffer to un-index the
+            // mesh, but `generate_tangents` requires indexed geometry;
+            // restore an identity index so flat-normal meshes still get
+            // tangents below.
+            let vertex_count = mesh.count_vertices() as u32;
+            mesh.set_indices(Some(Indices::U32((0..vertex_count).collect())));
+        }
+
+        let has_normal_map = model
+            .mesh
+            .material_id
+            .and_then(|id| mat_has_normal_map.get(id))
+            .copied()
+            .unwrap_or(false);
+        if has_normal_map {
+            generate_tangents(&mut mesh, &model.name);
         }
 
-        let mesh_handle =
-            load_context.set_labeled_asset(&mesh_label(model_idx), LoadedAsset::new(mesh));
+        let aabb = mesh.compute_aabb();
+        let label = mesh_label(&model.name, model_idx, &mut used_mesh_labels);
+        let mesh_handle = load_context.set_labeled_asset(&label, LoadedAsset::new(mesh));
 
         // Now assign the material
         let pbr_id = if let Some(mat_id) = model.mesh.material_id {
@@ -205,6 +399,15 @@ async fn load_obj_scene<'a, 'b>(
                 })
                 .id()
         };
+        if let Some(aabb) = aabb {
+            world.entity_mut(pbr_id).insert(aabb);
+        }
+        let name = if model.name.is_empty() {
+            model_idx.to_string()
+        } else {
+            model.name
+        };
+        world.entity_mut(pbr_id).insert(Name::new(name));
         world.entity_mut(world_id).push_children(&[pbr_id]);
     }
 